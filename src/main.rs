@@ -1,27 +1,28 @@
 //------------------------------------------------------------------------------
-// Rust DFS with Memoization for 3x3 Board State Exploration
+// Rust DFS with Memoization for Board State Exploration
 //
 // Approach:
-// This program represents a 3x3 board as a 64-bit integer where each cell is encoded using 4 bits.
-// It uses a Depth-First Search (DFS) algorithm with memoization to explore all possible board states
-// up to a given depth (or until the board is full). The DFS is implemented recursively, and it caches 
-// intermediate results in a HashMap to avoid redundant recalculations of the same state.
-// 
+// This program represents a rows x cols board (up to 16 cells, since each cell is encoded using 4
+// bits of a 64-bit integer) as a single u64. It uses a Depth-First Search (DFS) algorithm with
+// memoization to explore all possible board states up to a given depth (or until the board is
+// full). The DFS is implemented recursively, and it caches intermediate results in a HashMap to
+// avoid redundant recalculations of the same state.
+//
 // Algorithm:
 // 1. Encode the board in a 64-bit integer, each cell using 4 bits.
 // 2. Use helper functions `get_cell` and `set_cell` to read and modify the state.
-// 3. Implement DFS: For each state, if the maximum depth is reached or the board is full, compute a hash 
-//    of the board state and add it to the total; otherwise, for every empty cell, generate new states 
-//    based on the available moves. If a cell has at least two non-empty neighboring cells, apply valid 
+// 3. Implement DFS: For each state, if the maximum depth is reached or the board is full, compute a hash
+//    of the board state and add it to the total; otherwise, for every empty cell, generate new states
+//    based on the available moves. If a cell has at least two non-empty neighboring cells, apply valid
 //    merge combinations ("combos") to generate new states.
-// 4. Use memoization (hashing the state and current turn) to cache and quickly return results for states 
+// 4. Use memoization (hashing the state and current turn) to cache and quickly return results for states
 //    that have already been computed.
-// 
+//
 // Time Complexity:
 // Worst-case time complexity is exponential without memoization, but the memoization greatly prunes
-// redundant computations. In practice, the effective complexity depends on the number of unique states 
+// redundant computations. In practice, the effective complexity depends on the number of unique states
 // encountered.
-// 
+//
 // Space Complexity:
 // The algorithm uses O(m) space for memoization, where m is the number of unique states stored. The
 // recursion depth is O(max_depth), which is generally much smaller.
@@ -29,9 +30,11 @@
 //------------------------------------------------------------------------------
 
 // Import necessary standard library modules.
-use std::io::BufRead;
+use std::io::Read;
 use std::collections::HashMap;
 use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 //
 //=== FxHasher optimized for u64 ===//
@@ -80,49 +83,123 @@ impl BuildHasher for FxBuildHasher {
 }
 
 //
-//=== Constants and Definitions for the 3x3 Board ===//
+//=== Constants Shared Across Board Sizes ===//
 //
 
-const SIZE: usize = 3;
 const MODULO: u64 = 1 << 30;
 const MODULO_MASK: u64 = MODULO - 1;
-// Reserve 6 bits for the turn value in the memoization key.
-const TURN_BITS: u32 = 6;
-
-// Define the neighbors for each cell on the 3x3 board.
-const NEIGHBORS: [&[usize]; 9] = [
-    &[1, 3],
-    &[0, 2, 4],
-    &[1, 5],
-    &[0, 4, 6],
-    &[1, 3, 5, 7],
-    &[2, 4, 8],
-    &[3, 7],
-    &[4, 6, 8],
-    &[5, 7],
-];
-
-// Type alias for a slice of neighbor indices.
-type Combo = &'static [usize];
-
-// Predefined merge combinations for different neighbor counts.
-const COMBOS: [&[Combo]; 5] = [
-    &[], // 0 neighbor
-    &[], // 1 neighbor
-    &[&[0, 1]], // 2 neighbors
-    &[
-        &[0, 1],
-        &[0, 2],
-        &[1, 2],
-        &[0, 1, 2],
-    ], // 3 neighbors
-    &[
-        &[0, 1], &[0, 2], &[0, 3],
-        &[1, 2], &[1, 3], &[2, 3],
-        &[0, 1, 2], &[0, 1, 3], &[0, 2, 3], &[1, 2, 3],
-        &[0, 1, 2, 3],
-    ],
-];
+// A 4-bit-per-cell u64 state fits at most 16 cells.
+const MAX_CELLS: usize = 16;
+
+//
+//=== Board Geometry (generalized to rows x cols grids) ===//
+//
+// The board used to be hard-coded to a 3x3 grid with a static neighbor table
+// and static merge-combo tables. `Board` generates both at runtime from the
+// grid dimensions, so the same DFS engine handles any rows x cols grid whose
+// cell count fits in the 64-bit state (up to 4x4).
+
+struct Board {
+    rows: usize,
+    cols: usize,
+    cell_count: usize,
+    neighbors: Vec<Vec<usize>>,
+    // Has a `1` bit in the low bit of every cell's nibble; used by `is_full`.
+    full_mask: u64,
+    // Every size->=2 subset of `0..v_count`, indexed by `v_count` (0..=4).
+    // Precomputed once here instead of regenerated on every DFS step, since
+    // orthogonal grids never give a cell more than 4 neighbors.
+    combos_by_neighbor_count: [Vec<Vec<usize>>; 5],
+}
+
+impl Board {
+    fn new(rows: usize, cols: usize) -> Self {
+        let cell_count = rows * cols;
+        assert!(
+            (1..=MAX_CELLS).contains(&cell_count),
+            "board has {} cells; at most {} cells fit in a 4-bit-per-cell u64 state",
+            cell_count,
+            MAX_CELLS,
+        );
+
+        let neighbors = build_neighbors(rows, cols);
+
+        let mut full_mask = 0u64;
+        for i in 0..cell_count {
+            full_mask |= 1 << (i * 4);
+        }
+
+        let combos_by_neighbor_count = [
+            merge_combos(0),
+            merge_combos(1),
+            merge_combos(2),
+            merge_combos(3),
+            merge_combos(4),
+        ];
+
+        Board {
+            rows,
+            cols,
+            cell_count,
+            neighbors,
+            full_mask,
+            combos_by_neighbor_count,
+        }
+    }
+}
+
+impl Clone for Board {
+    fn clone(&self) -> Self {
+        Board {
+            rows: self.rows,
+            cols: self.cols,
+            cell_count: self.cell_count,
+            neighbors: self.neighbors.clone(),
+            full_mask: self.full_mask,
+            combos_by_neighbor_count: self.combos_by_neighbor_count.clone(),
+        }
+    }
+}
+
+// Builds the orthogonal (up, left, right, down) adjacency list for a
+// rows x cols grid from plain index arithmetic over row-major cell indices.
+fn build_neighbors(rows: usize, cols: usize) -> Vec<Vec<usize>> {
+    let mut neighbors = Vec::with_capacity(rows * cols);
+    for r in 0..rows {
+        for c in 0..cols {
+            let mut cell_neighbors = Vec::with_capacity(4);
+            if r > 0 {
+                cell_neighbors.push((r - 1) * cols + c);
+            }
+            if c > 0 {
+                cell_neighbors.push(r * cols + (c - 1));
+            }
+            if c + 1 < cols {
+                cell_neighbors.push(r * cols + (c + 1));
+            }
+            if r + 1 < rows {
+                cell_neighbors.push((r + 1) * cols + c);
+            }
+            neighbors.push(cell_neighbors);
+        }
+    }
+    neighbors
+}
+
+// Generates every size->=2 subset of `0..v_count` as a merge combo, replacing
+// the static per-neighbor-count `COMBOS` tables. Orthogonal grids never give
+// a cell more than 4 neighbors, so `v_count` never exceeds 4 regardless of
+// board size.
+fn merge_combos(v_count: usize) -> Vec<Vec<usize>> {
+    let mut combos = Vec::new();
+    for mask in 1u32..(1 << v_count) {
+        if mask.count_ones() < 2 {
+            continue;
+        }
+        combos.push((0..v_count).filter(|i| mask & (1 << i) != 0).collect());
+    }
+    combos
+}
 
 //
 //=== State Manipulation Functions (each cell uses 4 bits) ===//
@@ -143,19 +220,19 @@ fn set_cell(state: u64, idx: usize, value: u64) -> u64 {
 }
 
 #[inline(always)]
-fn is_full(state: u64) -> bool {
+fn is_full(state: u64, board: &Board) -> bool {
     // The board is considered full if every cell is non-zero.
     // This is determined by checking that each group of 4 bits has at least one bit set.
     let any_bit_set = state | (state >> 1) | (state >> 2) | (state >> 3);
-    (any_bit_set & 0x111111111) == 0x111111111
+    (any_bit_set & board.full_mask) == board.full_mask
 }
 
 #[inline(always)]
-fn compute_hash(state: u64) -> u64 {
+fn compute_hash(state: u64, board: &Board) -> u64 {
     // Compute a hash value for the board state.
     let mut s = state;
     let mut hash = 0u64;
-    for _ in 0..9 {
+    for _ in 0..board.cell_count {
         // Combine each cell value into the hash using a modulo-masked arithmetic.
         hash = (hash * 10 + (s & 0xF)) & MODULO_MASK;
         s >>= 4;
@@ -172,17 +249,22 @@ fn compute_hash(state: u64) -> u64 {
 // - state: current board state encoded in u64.
 // - turn: current DFS depth or move number.
 // - max_depth: maximum allowed depth for DFS.
+// - board: grid geometry (dimensions, neighbors, merge combos) being explored.
 // - memo: memoization table to cache results and avoid redundant computations.
 // - total: accumulator for the computed hash values (modulo MODULO_MASK).
-fn dfs(state: u64, turn: u64, max_depth: u64, memo: &mut HashMap<u64, u64, FxBuildHasher>, total: &mut u64) {
+fn dfs(state: u64, turn: u64, max_depth: u64, board: &Board, memo: &mut HashMap<(u64, u64), u64, FxBuildHasher>, total: &mut u64) {
     // If maximum depth is reached or the board is full, add the state's hash to total.
-    if turn == max_depth || is_full(state) {
-        *total = (*total + compute_hash(state)) & MODULO_MASK;
+    if turn == max_depth || is_full(state, board) {
+        *total = (*total + compute_hash(state, board)) & MODULO_MASK;
         return;
     }
 
-    // Create a unique key by combining state and turn.
-    let key = (state << TURN_BITS) | turn;
+    // Key the memo on the `(state, turn)` pair directly. A full 16-cell board
+    // already uses all 64 bits of `state`, leaving no headroom to bit-pack
+    // `turn` alongside it, but the pair doesn't need packing into a single
+    // u64 at all: a tuple key is just as cheap to hash and, unlike hashing
+    // the pair down to one u64, can never collide two distinct states.
+    let key = (state, turn);
     // Check if the result for this key is already computed.
     if let Some(&val) = memo.get(&key) {
         *total = (*total + val) & MODULO_MASK;
@@ -192,14 +274,14 @@ fn dfs(state: u64, turn: u64, max_depth: u64, memo: &mut HashMap<u64, u64, FxBui
     let start = *total;
 
     // Extract the board cells into a local array for repeated access.
-    let mut cells = [0u64; 9];
-    for i in 0..9 {
+    let mut cells = [0u64; MAX_CELLS];
+    for i in 0..board.cell_count {
         cells[i] = get_cell(state, i);
     }
 
     // Build a bitmask for empty cells: each bit corresponds to a cell being empty.
     let mut empty_mask: u16 = 0;
-    for idx in 0..9 {
+    for idx in 0..board.cell_count {
         empty_mask |= ((cells[idx] == 0) as u16) << idx;
     }
 
@@ -209,7 +291,7 @@ fn dfs(state: u64, turn: u64, max_depth: u64, memo: &mut HashMap<u64, u64, FxBui
         let idx = empty_mask.trailing_zeros() as usize;
         empty_mask &= empty_mask - 1;
 
-        let neighbors = NEIGHBORS[idx];
+        let neighbors = &board.neighbors[idx];
         let mut valid_values = [0u64; 4];
         let mut valid_masks = [0u64; 4];
         let mut v_count = 0;
@@ -226,15 +308,15 @@ fn dfs(state: u64, turn: u64, max_depth: u64, memo: &mut HashMap<u64, u64, FxBui
         // If fewer than two neighbors are non-empty, simply set the empty cell to 1.
         if v_count < 2 {
             let new_state = set_cell(state, idx, 1);
-            dfs(new_state, turn + 1, max_depth, memo, total);
+            dfs(new_state, turn + 1, max_depth, board, memo, total);
             continue;
         }
 
-        // Use predefined combos based on the number of non-empty neighbors.
-        let combos = COMBOS[v_count];
+        // Generate every size->=2 subset of the non-empty neighbors as a merge combo.
+        let combos = &board.combos_by_neighbor_count[v_count];
         let idx_shift = idx << 2;
         let mut found = false;
-        for &combo in combos {
+        for combo in combos {
             let mut sum = 0;
             for &i in combo {
                 sum += valid_values[i];
@@ -244,13 +326,13 @@ fn dfs(state: u64, turn: u64, max_depth: u64, memo: &mut HashMap<u64, u64, FxBui
 
             let mask = combo.iter().fold(0, |acc, &i| acc | valid_masks[i]);
             let new_state = (state & !mask) | (sum << idx_shift);
-            dfs(new_state, turn + 1, max_depth, memo, total);
+            dfs(new_state, turn + 1, max_depth, board, memo, total);
             found = true;
         }
         // If no combo was applicable, set the cell to the default value 1.
         if !found {
             let new_state = set_cell(state, idx, 1);
-            dfs(new_state, turn + 1, max_depth, memo, total);
+            dfs(new_state, turn + 1, max_depth, board, memo, total);
         }
     }
 
@@ -260,42 +342,604 @@ fn dfs(state: u64, turn: u64, max_depth: u64, memo: &mut HashMap<u64, u64, FxBui
 }
 
 //
-//=== Main Function ===//
+//=== Sharded Concurrent Memoization Table ===//
 //
-// Reads input from standard input.
-// The first line is the maximum depth (number of turns).
-// The following SIZE lines represent the board rows.
-// Then, the DFS is invoked to explore the states and compute the result,
-// which is finally printed to the standard output.
-fn main() -> std::io::Result<()> {
-    // Lire depuis input.txt
-    let stdin = std::io::stdin();
-    let mut lines = stdin.lock().lines();
+// A fixed number of shards, each guarded by its own Mutex, so independent
+// worker threads contend only when they happen to hash into the same shard.
+// SHARD_COUNT must stay a power of two: the shard is selected from the top
+// SHARD_BITS bits of the FxHasher output, which is cheap and spreads keys
+// evenly without a modulo.
+
+const SHARD_BITS: u32 = 4;
+const SHARD_COUNT: usize = 1 << SHARD_BITS;
 
-    let depth: u64 = lines.next().unwrap()?.trim().parse().unwrap();
+struct ShardedMemo {
+    shards: Vec<Mutex<HashMap<(u64, u64), u64, FxBuildHasher>>>,
+}
 
-    let mut initial_state: u64 = 0;
-    for i in 0..SIZE {
-        let row: Vec<u64> = lines.next().unwrap()?
-            .split_whitespace()
-            .map(|x| x.parse().unwrap())
+impl ShardedMemo {
+    fn new() -> Self {
+        let shards = (0..SHARD_COUNT)
+            .map(|_| Mutex::new(HashMap::with_capacity_and_hasher(1 << 12, FxBuildHasher)))
             .collect();
-        for j in 0..SIZE {
-            initial_state = set_cell(initial_state, i * SIZE + j, row[j]);
+        ShardedMemo { shards }
+    }
+
+    // Pick the shard for `(state, turn)` from the top bits of its FxHasher hash.
+    #[inline]
+    fn shard_for(key: (u64, u64)) -> usize {
+        (fx_hash_pair(key.0, key.1) >> (64 - SHARD_BITS)) as usize
+    }
+
+    fn get(&self, key: (u64, u64)) -> Option<u64> {
+        self.shards[Self::shard_for(key)].lock().unwrap().get(&key).copied()
+    }
+
+    fn insert(&self, key: (u64, u64), value: u64) {
+        self.shards[Self::shard_for(key)].lock().unwrap().insert(key, value);
+    }
+}
+
+//
+//=== Parallel Top-Level DFS ===//
+//
+
+// At or below this depth, thread spawn overhead dominates the actual work, so we
+// fall back to the plain serial `dfs` with a single-threaded HashMap.
+const PARALLEL_DEPTH_THRESHOLD: u64 = 3;
+
+// Expand the direct children of `state` for one ply, mirroring the move
+// generation performed inline inside `dfs`. Used to fan the first ply of
+// `dfs_parallel` out across worker threads.
+fn expand_children(state: u64, board: &Board) -> Vec<u64> {
+    let mut cells = [0u64; MAX_CELLS];
+    for i in 0..board.cell_count {
+        cells[i] = get_cell(state, i);
+    }
+
+    let mut empty_mask: u16 = 0;
+    for idx in 0..board.cell_count {
+        empty_mask |= ((cells[idx] == 0) as u16) << idx;
+    }
+
+    let mut children = Vec::new();
+    while empty_mask != 0 {
+        let idx = empty_mask.trailing_zeros() as usize;
+        empty_mask &= empty_mask - 1;
+
+        let neighbors = &board.neighbors[idx];
+        let mut valid_values = [0u64; 4];
+        let mut valid_masks = [0u64; 4];
+        let mut v_count = 0;
+        for &pos in neighbors {
+            if cells[pos] != 0 && cells[pos] != 6 {
+                valid_masks[v_count] = 0xF << (pos << 2);
+                valid_values[v_count] = cells[pos];
+                v_count += 1;
+            }
+        }
+
+        if v_count < 2 {
+            children.push(set_cell(state, idx, 1));
+            continue;
         }
+
+        let combos = &board.combos_by_neighbor_count[v_count];
+        let idx_shift = idx << 2;
+        let mut found = false;
+        for combo in combos {
+            let mut sum = 0;
+            for &i in combo {
+                sum += valid_values[i];
+                if sum > 6 { break; }
+            }
+            if sum > 6 { continue; }
+
+            let mask = combo.iter().fold(0, |acc, &i| acc | valid_masks[i]);
+            children.push((state & !mask) | (sum << idx_shift));
+            found = true;
+        }
+        if !found {
+            children.push(set_cell(state, idx, 1));
+        }
+    }
+
+    children
+}
+
+// Thread-safe twin of `dfs`: identical traversal and incremental-caching
+// logic, but reading and writing through a `ShardedMemo` shared (via `Arc`)
+// across worker threads instead of an exclusively-owned `HashMap`.
+fn dfs_shared(state: u64, turn: u64, max_depth: u64, board: &Board, memo: &ShardedMemo, total: &mut u64) {
+    if turn == max_depth || is_full(state, board) {
+        *total = (*total + compute_hash(state, board)) & MODULO_MASK;
+        return;
+    }
+
+    let key = (state, turn);
+    if let Some(val) = memo.get(key) {
+        *total = (*total + val) & MODULO_MASK;
+        return;
+    }
+
+    let start = *total;
+    for child in expand_children(state, board) {
+        dfs_shared(child, turn + 1, max_depth, board, memo, total);
+    }
+
+    let val = (*total + MODULO - start) & MODULO_MASK;
+    memo.insert(key, val);
+}
+
+// Entry point that parallelizes the first ply of the search across worker
+// threads. Every first-level child state is independent, and the partial
+// sums they produce are combined with `(a + b) & MODULO_MASK`, which is
+// commutative, so the combination order doesn't affect the result. All
+// workers share memoized subtrees through a `ShardedMemo`.
+fn dfs_parallel(initial_state: u64, max_depth: u64, board: &Board) -> u64 {
+    if max_depth <= PARALLEL_DEPTH_THRESHOLD {
+        let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+            HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher);
+        let mut total = 0;
+        dfs(initial_state, 0, max_depth, board, &mut memo, &mut total);
+        return total;
+    }
+
+    if is_full(initial_state, board) {
+        return compute_hash(initial_state, board) & MODULO_MASK;
+    }
+
+    let memo = Arc::new(ShardedMemo::new());
+    let board = Arc::new(board.clone());
+
+    let handles: Vec<_> = expand_children(initial_state, &board)
+        .into_iter()
+        .map(|child| {
+            let memo = Arc::clone(&memo);
+            let board = Arc::clone(&board);
+            thread::spawn(move || {
+                let mut partial = 0u64;
+                dfs_shared(child, 1, max_depth, &board, &memo, &mut partial);
+                partial
+            })
+        })
+        .collect();
+
+    let mut total = 0u64;
+    for handle in handles {
+        let partial = handle.join().expect("worker thread panicked");
+        total = (total + partial) & MODULO_MASK;
+    }
+
+    total
+}
+
+//
+//=== Bounded-Memory Memoization with LRU Eviction ===//
+//
+// Every memo value is a pure function of its `(state, turn, max_depth)` key,
+// so evicting an entry only costs recomputation time if it's ever needed
+// again, never correctness. `LruMemo` wraps the usual `HashMap` with an
+// intrusive doubly linked list (slab-backed, so nodes are reused instead of
+// reallocated) tracking usage order: `get` moves the touched key to the
+// front, and `insert` evicts the tail once the map reaches capacity.
+
+const NULL: usize = usize::MAX;
+
+#[derive(Clone, Copy)]
+struct LruNode {
+    key: (u64, u64),
+    value: u64,
+    prev: usize,
+    next: usize,
+}
+
+struct LruMemo {
+    map: HashMap<(u64, u64), usize, FxBuildHasher>,
+    nodes: Vec<LruNode>,
+    free: Vec<usize>,
+    head: usize, // most-recently-used
+    tail: usize, // least-recently-used
+    cap: Option<usize>, // None means unbounded, preserving current behavior
+}
+
+impl LruMemo {
+    fn new(cap: Option<usize>) -> Self {
+        LruMemo {
+            map: HashMap::default(),
+            nodes: Vec::new(),
+            free: Vec::new(),
+            head: NULL,
+            tail: NULL,
+            cap,
+        }
+    }
+
+    // Remove node `idx` from the linked list without freeing its slot.
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = (self.nodes[idx].prev, self.nodes[idx].next);
+        if prev != NULL {
+            self.nodes[prev].next = next;
+        } else {
+            self.head = next;
+        }
+        if next != NULL {
+            self.nodes[next].prev = prev;
+        } else {
+            self.tail = prev;
+        }
+    }
+
+    // Insert node `idx` at the most-recently-used end.
+    fn push_front(&mut self, idx: usize) {
+        self.nodes[idx].prev = NULL;
+        self.nodes[idx].next = self.head;
+        if self.head != NULL {
+            self.nodes[self.head].prev = idx;
+        }
+        self.head = idx;
+        if self.tail == NULL {
+            self.tail = idx;
+        }
+    }
+
+    // Move an already-linked node to the most-recently-used end.
+    fn touch(&mut self, idx: usize) {
+        if self.head == idx {
+            return;
+        }
+        self.unlink(idx);
+        self.push_front(idx);
+    }
+
+    fn get(&mut self, key: (u64, u64)) -> Option<u64> {
+        let idx = *self.map.get(&key)?;
+        self.touch(idx);
+        Some(self.nodes[idx].value)
+    }
+
+    fn insert(&mut self, key: (u64, u64), value: u64) {
+        if let Some(&idx) = self.map.get(&key) {
+            self.nodes[idx].value = value;
+            self.touch(idx);
+            return;
+        }
+
+        if let Some(cap) = self.cap {
+            if cap == 0 {
+                return;
+            }
+            if self.map.len() >= cap {
+                self.evict_lru();
+            }
+        }
+
+        let node = LruNode { key, value, prev: NULL, next: NULL };
+        let idx = if let Some(free_idx) = self.free.pop() {
+            self.nodes[free_idx] = node;
+            free_idx
+        } else {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        };
+        self.map.insert(key, idx);
+        self.push_front(idx);
+    }
+
+    fn evict_lru(&mut self) {
+        if self.tail == NULL {
+            return;
+        }
+        let idx = self.tail;
+        self.unlink(idx);
+        self.map.remove(&self.nodes[idx].key);
+        self.free.push(idx);
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+// Serial twin of `dfs` backed by a capped, LRU-evicting memo instead of a
+// plain `HashMap`.
+fn dfs_lru(state: u64, turn: u64, max_depth: u64, board: &Board, memo: &mut LruMemo, total: &mut u64) {
+    if turn == max_depth || is_full(state, board) {
+        *total = (*total + compute_hash(state, board)) & MODULO_MASK;
+        return;
     }
-    
-    // Create a HashMap for memoization with a custom hasher and preallocated capacity.
-    let mut memo: HashMap<u64, u64, FxBuildHasher> =
-        HashMap::with_capacity_and_hasher(1 << 16 , FxBuildHasher::default());
 
+    let key = (state, turn);
+    if let Some(val) = memo.get(key) {
+        *total = (*total + val) & MODULO_MASK;
+        return;
+    }
+
+    let start = *total;
+    for child in expand_children(state, board) {
+        dfs_lru(child, turn + 1, max_depth, board, memo, total);
+    }
+
+    let val = (*total + MODULO - start) & MODULO_MASK;
+    memo.insert(key, val);
+}
+
+// Entry point for bounded-memory runs: `cap` limits the memo to at most
+// `cap` live entries, evicting the least-recently-used one on overflow.
+fn dfs_bounded(initial_state: u64, max_depth: u64, board: &Board, cap: usize) -> u64 {
+    let mut memo = LruMemo::new(Some(cap));
     let mut total = 0;
-    dfs(initial_state, 0, depth, &mut memo, &mut total);
-    
+    dfs_lru(initial_state, 0, max_depth, board, &mut memo, &mut total);
+    total
+}
+
+// Reads the optional memo capacity from the `MEMO_CAP` environment
+// variable. Unset or unparsable leaves the memo unbounded, preserving
+// current behavior.
+fn memo_cap_from_env() -> Option<usize> {
+    std::env::var("MEMO_CAP").ok().and_then(|v| v.trim().parse().ok())
+}
+
+//
+//=== Persistent On-Disk Memo Index ===//
+//
+// Layout: a 40-byte header `{magic: u64, rows: u64, cols: u64, max_depth:
+// u64, entry_count: u64}` followed by an open-addressed flat table of
+// `(state: u64, turn: u64, value: u64)` records (empty slots hold
+// `EMPTY_SLOT` as their `state` field — no reachable board ever encodes to
+// `u64::MAX`, since every cell only ever holds a value in `0..=6`), all
+// little-endian. Open addressing keeps the table a single contiguous array
+// that can be probed directly without rehashing into a HashMap, so it's
+// equally happy read fully into memory or memory-mapped.
+//
+// Memo values are only valid for the board dimensions and `max_depth` they
+// were computed with (`dfs` terminates at `turn == max_depth`), so a cache
+// file tagged with different dimensions or depth is ignored rather than
+// trusted.
+
+const CACHE_MAGIC: u64 = 0x4D454D4F_43414348; // "MEMOCACH" in ASCII
+const EMPTY_SLOT: u64 = u64::MAX;
+
+// Hashes `(state, turn)` together. This is used to pick a probe start in the
+// cache's open-addressed table and to pick a `ShardedMemo` shard, never as
+// the memo key itself, so a hash collision here just means a longer probe
+// chain or a shared shard, never a wrong lookup.
+#[inline]
+fn fx_hash_pair(state: u64, turn: u64) -> u64 {
+    let mut hasher = FxBuildHasher.build_hasher();
+    hasher.write_u64(state);
+    hasher.write_u64(turn);
+    hasher.finish()
+}
+
+// Loads a memo index from `path` if it exists and was computed for
+// `board`'s dimensions and `max_depth`. Returns `None` on any mismatch,
+// I/O error, or malformed file, in which case the caller should fall back
+// to an empty memo.
+fn load_memo_index(path: &str, board: &Board, max_depth: u64) -> Option<HashMap<(u64, u64), u64, FxBuildHasher>> {
+    let data = std::fs::read(path).ok()?;
+    if data.len() < 40 {
+        return None;
+    }
+
+    let magic = u64::from_le_bytes(data[0..8].try_into().ok()?);
+    let file_rows = u64::from_le_bytes(data[8..16].try_into().ok()?);
+    let file_cols = u64::from_le_bytes(data[16..24].try_into().ok()?);
+    let file_depth = u64::from_le_bytes(data[24..32].try_into().ok()?);
+    let entry_count = u64::from_le_bytes(data[32..40].try_into().ok()?) as usize;
+    if magic != CACHE_MAGIC
+        || file_rows != board.rows as u64
+        || file_cols != board.cols as u64
+        || file_depth != max_depth
+    {
+        return None;
+    }
+
+    if (data.len() - 40) % 24 != 0 {
+        return None;
+    }
+    let slot_count = (data.len() - 40) / 24;
+    let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+        HashMap::with_capacity_and_hasher(entry_count.max(1), FxBuildHasher);
+    for i in 0..slot_count {
+        let off = 40 + i * 24;
+        let state = u64::from_le_bytes(data[off..off + 8].try_into().ok()?);
+        if state == EMPTY_SLOT {
+            continue;
+        }
+        let turn = u64::from_le_bytes(data[off + 8..off + 16].try_into().ok()?);
+        let value = u64::from_le_bytes(data[off + 16..off + 24].try_into().ok()?);
+        memo.insert((state, turn), value);
+    }
+    if memo.len() != entry_count {
+        return None;
+    }
+    Some(memo)
+}
+
+// Writes `memo` to `path` as an open-addressed table tagged with `board`'s
+// dimensions and `max_depth`, sized so it is at most half full (short
+// probe sequences on the next load).
+fn save_memo_index(path: &str, board: &Board, max_depth: u64, memo: &HashMap<(u64, u64), u64, FxBuildHasher>) -> std::io::Result<()> {
+    let mut slot_count = 16usize;
+    while memo.len() * 2 > slot_count {
+        slot_count *= 2;
+    }
+
+    let mut table = vec![EMPTY_SLOT; slot_count * 3]; // interleaved (state, turn, value) triples
+    for (&(state, turn), &value) in memo.iter() {
+        let mut idx = (fx_hash_pair(state, turn) as usize) & (slot_count - 1);
+        loop {
+            if table[idx * 3] == EMPTY_SLOT {
+                table[idx * 3] = state;
+                table[idx * 3 + 1] = turn;
+                table[idx * 3 + 2] = value;
+                break;
+            }
+            idx = (idx + 1) & (slot_count - 1);
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(40 + table.len() * 8);
+    bytes.extend_from_slice(&CACHE_MAGIC.to_le_bytes());
+    bytes.extend_from_slice(&(board.rows as u64).to_le_bytes());
+    bytes.extend_from_slice(&(board.cols as u64).to_le_bytes());
+    bytes.extend_from_slice(&max_depth.to_le_bytes());
+    bytes.extend_from_slice(&(memo.len() as u64).to_le_bytes());
+    for v in &table {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    std::fs::write(path, bytes)
+}
+
+// Scans argv for `--cache <path>`, returning the path if present.
+fn parse_cache_arg() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--cache" {
+            return args.next();
+        }
+    }
+    None
+}
+
+// Scans argv for the `--batch` flag.
+fn has_batch_flag() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--batch")
+}
+
+//
+//=== Byte-Level Whitespace Tokenizer ===//
+//
+// Slurps all of stdin into a single buffer and yields `u64` tokens by
+// skipping ASCII whitespace and folding consecutive digits, as
+// competitive-programming input readers do. Avoids the per-line
+// allocation and UTF-8 validation that `BufRead::lines` pays for.
+
+struct Tokenizer {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl Tokenizer {
+    fn new(data: Vec<u8>) -> Self {
+        Tokenizer { data, pos: 0 }
+    }
+
+    fn next_u64(&mut self) -> Option<u64> {
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let mut value = 0u64;
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_digit() {
+            value = value * 10 + (self.data[self.pos] - b'0') as u64;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
+
+// Reads one `rows`, `cols`, `depth`, and `rows * cols` board cells (row-major)
+// from `tokens` and packs the board into the encoded state.
+fn read_case(tokens: &mut Tokenizer) -> Option<(usize, usize, u64, u64)> {
+    let rows = tokens.next_u64()? as usize;
+    let cols = tokens.next_u64()? as usize;
+    let depth = tokens.next_u64()?;
+    let mut state: u64 = 0;
+    for i in 0..rows * cols {
+        state = set_cell(state, i, tokens.next_u64()?);
+    }
+    Some((rows, cols, depth, state))
+}
+
+// Runs every case in `cases` through `dfs`, reusing one memo table and
+// `Board` across consecutive cases that share the same dimensions and
+// depth, and rebuilding the `Board` / clearing the memo whenever either
+// changes (memo values are only valid for the board dimensions and
+// `max_depth` they were computed with).
+fn run_batch(cases: &[(usize, usize, u64, u64)]) -> Vec<u64> {
+    let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+        HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher);
+    let mut board: Option<Board> = None;
+    let mut last_depth = None;
+    let mut results = Vec::with_capacity(cases.len());
+
+    for &(rows, cols, depth, state) in cases {
+        let dims_changed = board.as_ref().is_none_or(|b| b.rows != rows || b.cols != cols);
+        if dims_changed {
+            board = Some(Board::new(rows, cols));
+            memo.clear();
+        } else if last_depth != Some(depth) {
+            memo.clear();
+        }
+        last_depth = Some(depth);
+
+        let mut total = 0;
+        dfs(state, 0, depth, board.as_ref().unwrap(), &mut memo, &mut total);
+        results.push(total);
+    }
+
+    results
+}
+
+//
+//=== Main Function ===
+//
+// Reads input from standard input via the byte-level tokenizer.
+// Normal mode: `rows`, `cols`, `depth`, then `rows * cols` board cells,
+// one DFS result printed to standard output.
+// Batch mode (`--batch`): a leading test case count `T`, followed by `T`
+// blocks in the same `rows cols depth` + cells shape, emitting one result
+// line per case.
+fn main() -> std::io::Result<()> {
+    let cache_path = parse_cache_arg();
+    let batch_mode = has_batch_flag();
+
+    let mut input = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut input)?;
+    let mut tokens = Tokenizer::new(input);
+
+    if batch_mode {
+        let case_count = tokens.next_u64().expect("missing test case count") as usize;
+        let mut cases = Vec::with_capacity(case_count);
+        for _ in 0..case_count {
+            cases.push(read_case(&mut tokens).expect("truncated test case"));
+        }
+
+        for total in run_batch(&cases) {
+            println!("{}", total);
+        }
+        return Ok(());
+    }
+
+    let (rows, cols, depth, initial_state) = read_case(&mut tokens).expect("missing test case");
+    let board = Board::new(rows, cols);
+
+    let total = if let Some(path) = &cache_path {
+        let mut memo = load_memo_index(path, &board, depth)
+            .unwrap_or_else(|| HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher));
+        let mut total = 0;
+        dfs(initial_state, 0, depth, &board, &mut memo, &mut total);
+        if let Err(e) = save_memo_index(path, &board, depth, &memo) {
+            eprintln!("warning: failed to write memo cache to {}: {}", path, e);
+        }
+        total
+    } else {
+        match memo_cap_from_env() {
+            Some(cap) => dfs_bounded(initial_state, depth, &board, cap),
+            None => dfs_parallel(initial_state, depth, &board),
+        }
+    };
+
     println!("{}", total);
 
     Ok(())
-    
+
 }
 
 #[cfg(test)]
@@ -313,15 +957,17 @@ mod tests {
         // Read the depth from the first line
         let depth: u64 = lines.next().unwrap().unwrap().trim().parse().unwrap();
 
-        // Read the 3x3 board
+        // Read the 3x3 board. These fixtures predate the generalized engine
+        // and are always a fixed 3x3 grid, independent of main's stdin format.
+        let board = Board::new(3, 3);
         let mut initial_state: u64 = 0;
-        for i in 0..SIZE {
+        for i in 0..3 {
             let row: Vec<u64> = lines.next().unwrap().unwrap()
                 .split_whitespace()
                 .map(|x| x.parse().unwrap())
                 .collect();
-            for j in 0..SIZE {
-                initial_state = set_cell(initial_state, i * SIZE + j, row[j]);
+            for j in 0..3 {
+                initial_state = set_cell(initial_state, i * 3 + j, row[j]);
             }
         }
 
@@ -329,10 +975,10 @@ mod tests {
         let expected: u64 = lines.next().unwrap().unwrap().trim().parse().unwrap();
 
         // Run the DFS
-        let mut memo: HashMap<u64, u64, FxBuildHasher> =
-            HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher::default());
+        let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+            HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher);
         let mut total = 0;
-        dfs(initial_state, 0, depth, &mut memo, &mut total);
+        dfs(initial_state, 0, depth, &board, &mut memo, &mut total);
 
         // Print and compare results
         println!("[{}] Expected: {}", path, expected);
@@ -382,4 +1028,239 @@ mod tests {
             run_test_case(file);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_dfs_parallel_matches_serial() {
+        // 3x3 all-merge board (1s on every even cell, so every empty cell
+        // has at least two mergeable neighbors), deep enough to take the
+        // threaded path.
+        let board = Board::new(3, 3);
+        let mut state: u64 = 0;
+        for i in (0..9).step_by(2) {
+            state = set_cell(state, i, 1);
+        }
+        let depth = 6;
+
+        let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+            HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher);
+        let mut expected = 0;
+        dfs(state, 0, depth, &board, &mut memo, &mut expected);
+
+        assert_eq!(dfs_parallel(state, depth, &board), expected);
+    }
+
+    #[test]
+    fn test_dfs_bounded_matches_unbounded() {
+        // 3x3 all-merge board (1s on every even cell); capped tight enough
+        // to force evictions.
+        let board = Board::new(3, 3);
+        let mut state: u64 = 0;
+        for i in (0..9).step_by(2) {
+            state = set_cell(state, i, 1);
+        }
+        let depth = 6;
+
+        let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+            HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher);
+        let mut expected = 0;
+        dfs(state, 0, depth, &board, &mut memo, &mut expected);
+
+        assert_eq!(dfs_bounded(state, depth, &board, 4), expected);
+    }
+
+    #[test]
+    fn test_lru_memo_evicts_least_recently_used() {
+        let mut memo = LruMemo::new(Some(2));
+        memo.insert((1, 0), 10);
+        memo.insert((2, 0), 20);
+        // Touch key (1, 0) so (2, 0) becomes the least-recently-used entry.
+        assert_eq!(memo.get((1, 0)), Some(10));
+        memo.insert((3, 0), 30);
+
+        assert_eq!(memo.len(), 2);
+        assert_eq!(memo.get((2, 0)), None);
+        assert_eq!(memo.get((1, 0)), Some(10));
+        assert_eq!(memo.get((3, 0)), Some(30));
+    }
+
+    #[test]
+    fn test_memo_cache_roundtrip() {
+        // Not a full board, so the DFS actually fills the memo.
+        let board = Board::new(3, 3);
+        let mut state: u64 = 0;
+        for i in (0..9).step_by(2) {
+            state = set_cell(state, i, 1);
+        }
+        let depth = 4;
+
+        let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+            HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher);
+        let mut total = 0;
+        dfs(state, 0, depth, &board, &mut memo, &mut total);
+
+        let path = std::env::temp_dir().join(format!("memo_cache_test_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        save_memo_index(path, &board, depth, &memo).unwrap();
+
+        let loaded = load_memo_index(path, &board, depth).expect("cache should load back");
+        assert_eq!(loaded.len(), memo.len());
+        for (key, value) in &memo {
+            assert_eq!(loaded.get(key), Some(value));
+        }
+
+        // A cache tagged with a different depth must be rejected.
+        assert!(load_memo_index(path, &board, depth + 1).is_none());
+
+        // A cache tagged with different board dimensions must also be rejected.
+        let other_board = Board::new(2, 2);
+        assert!(load_memo_index(path, &other_board, depth).is_none());
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_tokenizer_folds_digits_across_whitespace() {
+        let mut tokens = Tokenizer::new(b"  12\t3\n  456 ".to_vec());
+        assert_eq!(tokens.next_u64(), Some(12));
+        assert_eq!(tokens.next_u64(), Some(3));
+        assert_eq!(tokens.next_u64(), Some(456));
+        assert_eq!(tokens.next_u64(), None);
+    }
+
+    #[test]
+    fn test_read_case_and_batch_header_roundtrip() {
+        // Exercises the documented stdin contract end to end: a leading
+        // test case count, then that many `rows cols depth` + cells blocks,
+        // through the byte tokenizer and `read_case` rather than by
+        // constructing `(rows, cols, depth, state)` tuples by hand.
+        let input = b"2\n\
+                      3 3 2\n\
+                      1 2 3\n\
+                      4 5 6\n\
+                      1 2 3\n\
+                      2 2 3\n\
+                      1 1\n\
+                      1 1\n";
+        let mut tokens = Tokenizer::new(input.to_vec());
+
+        let case_count = tokens.next_u64().expect("missing test case count") as usize;
+        assert_eq!(case_count, 2);
+
+        let mut cases = Vec::with_capacity(case_count);
+        for _ in 0..case_count {
+            cases.push(read_case(&mut tokens).expect("truncated test case"));
+        }
+
+        let (rows, cols, depth, state) = cases[0];
+        assert_eq!((rows, cols, depth), (3, 3, 2));
+        let mut expected_state = 0u64;
+        for (i, &v) in [1, 2, 3, 4, 5, 6, 1, 2, 3].iter().enumerate() {
+            expected_state = set_cell(expected_state, i, v);
+        }
+        assert_eq!(state, expected_state);
+
+        let (rows, cols, depth, state) = cases[1];
+        assert_eq!((rows, cols, depth, state), (2, 2, 3, {
+            let mut s = 0u64;
+            for i in 0..4 {
+                s = set_cell(s, i, 1);
+            }
+            s
+        }));
+
+        let results = run_batch(&cases);
+        for (&(rows, cols, depth, state), &got) in cases.iter().zip(results.iter()) {
+            let board = Board::new(rows, cols);
+            let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+                HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher);
+            let mut expected = 0;
+            dfs(state, 0, depth, &board, &mut memo, &mut expected);
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn test_run_batch_matches_individual_dfs_calls() {
+        let cases = vec![
+            (3, 3, 2, {
+                let mut s = 0u64;
+                for i in 0..9 {
+                    s = set_cell(s, i, ((i % 6) + 1) as u64);
+                }
+                s
+            }),
+            (3, 3, 2, 0u64), // same dims and depth: memo stays warm
+            (3, 3, 3, 0u64), // different depth: memo must be cleared first
+        ];
+
+        let results = run_batch(&cases);
+
+        for (&(rows, cols, depth, state), &got) in cases.iter().zip(results.iter()) {
+            let board = Board::new(rows, cols);
+            let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+                HashMap::with_capacity_and_hasher(1 << 16, FxBuildHasher);
+            let mut expected = 0;
+            dfs(state, 0, depth, &board, &mut memo, &mut expected);
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn test_board_neighbors_match_original_3x3_table() {
+        let board = Board::new(3, 3);
+        let expected: [&[usize]; 9] = [
+            &[1, 3],
+            &[0, 2, 4],
+            &[1, 5],
+            &[0, 4, 6],
+            &[1, 3, 5, 7],
+            &[2, 4, 8],
+            &[3, 7],
+            &[4, 6, 8],
+            &[5, 7],
+        ];
+        for (idx, expected_neighbors) in expected.iter().enumerate() {
+            assert_eq!(&board.neighbors[idx], expected_neighbors);
+        }
+    }
+
+    #[test]
+    fn test_dfs_on_non_square_board() {
+        // 2x3 board, fully filled: is_full and compute_hash must be correct
+        // for a cell count other than 9.
+        let board = Board::new(2, 3);
+        let mut state: u64 = 0;
+        for i in 0..6 {
+            state = set_cell(state, i, 1);
+        }
+
+        let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+            HashMap::with_capacity_and_hasher(1 << 12, FxBuildHasher);
+        let mut total = 0;
+        dfs(state, 0, 5, &board, &mut memo, &mut total);
+
+        assert_eq!(total, compute_hash(state, &board) & MODULO_MASK);
+    }
+
+    #[test]
+    fn test_dfs_at_max_cell_count_does_not_collide_across_turns() {
+        // A full 4x4 board has 16 cells, the maximum this u64 state can
+        // hold. The memo key is the `(state, turn)` pair itself rather than
+        // a value bit-packed or hashed into a single u64, so there's no
+        // headroom concern at this size: two entries with the same `state`
+        // but different `turn` are still distinct map keys by construction.
+        let board = Board::new(4, 4);
+        let mut state: u64 = 0;
+        for i in 0..16 {
+            state = set_cell(state, i, 1);
+        }
+
+        let mut memo: HashMap<(u64, u64), u64, FxBuildHasher> =
+            HashMap::with_capacity_and_hasher(1 << 12, FxBuildHasher);
+        let mut total = 0;
+        dfs(state, 0, 3, &board, &mut memo, &mut total);
+
+        assert_eq!(total, compute_hash(state, &board) & MODULO_MASK);
+        assert_ne!((state, 0u64), (state, 1u64));
+    }
+}